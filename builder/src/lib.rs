@@ -1,207 +1,772 @@
-use core::alloc;
-
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{
-    parse::{Parse, ParseStream},
-    LitStr, Token,
-};
-use syn::{parse_macro_input, DeriveInput, Ident};
-
-fn is_a(to_match_on: String, ty: &syn::Type) -> bool {
-    if let Some(ident) = get_ident_from_type(ty) {
-        return ident.to_string() == to_match_on;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, DeriveInput, Expr, Ident, LitStr, Token};
+
+/// Returns the *last* segment of `ty`'s path, so `std::option::Option<T>`
+/// and `core::option::Option<T>` are recognized the same as a bare
+/// `Option<T>`. Returns `None` for anything that isn't a path type.
+fn get_last_segment(ty: &syn::Type) -> Option<&syn::PathSegment> {
+    match ty {
+        syn::Type::Path(path) => path.path.segments.last(),
+        _ => None,
     }
+}
 
-    false
+fn is_a(to_match_on: &str, ty: &syn::Type) -> bool {
+    get_ident_from_type(ty).is_some_and(|ident| ident == to_match_on)
 }
 
 fn get_ident_from_type(ty: &syn::Type) -> Option<syn::Ident> {
-    if let syn::Type::Path(path) = ty {
-        if let Some(ident) = &path.path.segments.first() {
-            return Some(ident.ident.clone());
+    get_last_segment(ty).map(|segment| segment.ident.clone())
+}
+
+/// Returns the first generic type argument of `ty`, e.g. `T` for `Vec<T>`.
+/// Returns `None` instead of panicking for bare idents, tuples, and any
+/// other type with no angle-bracketed arguments.
+fn get_in_angle_bracket(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::PathArguments::AngleBracketed(angle) = &get_last_segment(ty)?.arguments else {
+        return None;
+    };
+
+    angle.args.iter().find_map(|arg| match arg {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+/// Smart-pointer wrappers that may transparently wrap an `Option<T>` or
+/// `Vec<T>` field, e.g. `Box<Option<String>>`. A new wrapper is recognized
+/// by adding one entry here and a matching arm in `rewrap`.
+const TRANSPARENT_WRAPPERS: &[&str] = &["Box", "Arc", "Rc"];
+
+/// If `ty`'s last segment is one of `TRANSPARENT_WRAPPERS`, returns that
+/// wrapper's name together with the type it wraps.
+fn unwrap_transparent(ty: &syn::Type) -> Option<(&'static str, &syn::Type)> {
+    let segment = get_last_segment(ty)?;
+    let wrapper = *TRANSPARENT_WRAPPERS
+        .iter()
+        .find(|name| segment.ident == **name)?;
+
+    Some((wrapper, get_in_angle_bracket(ty)?))
+}
+
+/// Re-wraps `value` in the smart pointer that `unwrap_transparent` peeled
+/// off, e.g. turns `self.name.clone()` back into `Box::new(self.name.clone())`.
+fn rewrap(wrapper: &str, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    match wrapper {
+        "Box" => quote! { ::std::boxed::Box::new(#value) },
+        "Arc" => quote! { ::std::sync::Arc::new(#value) },
+        "Rc" => quote! { ::std::rc::Rc::new(#value) },
+        _ => unreachable!("unrecognized transparent wrapper"),
+    }
+}
+
+/// The value of a `#[builder(default ...)]` key: either a bare `default`,
+/// which falls back to `Default::default()`, or `default = "expr"`, which
+/// falls back to the parsed expression.
+enum DefaultValue {
+    Bare,
+    Expr(LitStr),
+}
+
+/// The recognized contents of one or more `#[builder(...)]` attributes on a
+/// single field, collected into a single struct so every key can be parsed
+/// in one pass instead of only looking at the first attribute.
+#[derive(Default)]
+struct FieldOpts {
+    each: Option<LitStr>,
+    rename: Option<LitStr>,
+    default: Option<DefaultValue>,
+    setter_prefix: Option<LitStr>,
+}
+
+impl FieldOpts {
+    /// Walk every `#[builder(...)]` attribute on `attrs` and collect the
+    /// recognized keys. A field with no `#[builder]` attribute at all gets a
+    /// default-filled `FieldOpts` rather than `None`.
+    fn from_attrs(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut opts = FieldOpts::default();
+
+        for attr in attrs {
+            if !attr.path().is_ident("builder") {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("each") {
+                    opts.each = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("rename") {
+                    opts.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    opts.default = Some(if meta.input.peek(Token![=]) {
+                        DefaultValue::Expr(meta.value()?.parse()?)
+                    } else {
+                        DefaultValue::Bare
+                    });
+                } else if meta.path.is_ident("setter_prefix") {
+                    opts.setter_prefix = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown builder key"));
+                }
+
+                Ok(())
+            })?;
         }
+
+        Ok(opts)
     }
-    None
 }
 
-fn get_in_angle_bracket(ty: &syn::Type) -> Option<syn::Ident> {
-    if let syn::Type::Path(path) = ty {
-        if let syn::PathArguments::AngleBracketed(angle) =
-            &path.path.segments.first().unwrap().arguments
-        {
-            let args = angle.args.first().unwrap();
-            if let syn::GenericArgument::Type(ty) = args {
-                let ident = get_ident_from_type(ty).unwrap();
+/// The recognized contents of the struct-level `#[builder(...)]` attribute.
+#[derive(Default)]
+struct StructOpts {
+    typestate: bool,
+    setters: Option<LitStr>,
+}
+
+impl StructOpts {
+    fn from_attrs(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut opts = StructOpts::default();
 
-                return Some(ident);
+        for attr in attrs {
+            if !attr.path().is_ident("builder") {
+                continue;
             }
-            return None;
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("typestate") {
+                    opts.typestate = true;
+                } else if meta.path.is_ident("setters") {
+                    opts.setters = Some(meta.value()?.parse()?);
+                } else {
+                    return Err(meta.error("unknown builder key"));
+                }
+
+                Ok(())
+            })?;
         }
-        return None;
+
+        Ok(opts)
+    }
+}
+
+/// A `#[builder(setters = "...")]` case-conversion policy for generated
+/// setter method names. Kebab case is converted via `heck` and then mapped
+/// to snake case, since a hyphen isn't a valid identifier character.
+enum SetterCase {
+    Snake,
+    LowerCamel,
+    UpperCamel,
+    Kebab,
+}
+
+impl SetterCase {
+    fn parse(policy: &str) -> Option<Self> {
+        match policy {
+            "snake_case" => Some(Self::Snake),
+            "camelCase" | "lowerCamelCase" => Some(Self::LowerCamel),
+            "PascalCase" | "UpperCamelCase" => Some(Self::UpperCamel),
+            "kebab-case" => Some(Self::Kebab),
+            _ => None,
+        }
+    }
+
+    /// Whether this policy can produce an identifier that isn't snake_case,
+    /// and therefore needs `#[allow(non_snake_case)]` on the generated setter.
+    fn is_non_snake(&self) -> bool {
+        !matches!(self, Self::Snake | Self::Kebab)
+    }
+
+    fn convert(&self, ident: &str) -> String {
+        use heck::{ToKebabCase, ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
+
+        match self {
+            Self::Snake => ident.to_snake_case(),
+            Self::LowerCamel => ident.to_lower_camel_case(),
+            Self::UpperCamel => ident.to_upper_camel_case(),
+            Self::Kebab => ident.to_kebab_case().replace('-', "_"),
+        }
+    }
+}
+
+/// Resolves the public setter method identifier for a field: an explicit
+/// `#[builder(setter_prefix = "...")]` is prepended first, then an explicit
+/// `#[builder(rename = "...")]` wins outright over any case policy,
+/// otherwise a struct-level case policy is applied to the (possibly
+/// prefixed) field name, otherwise the field's own name is used as-is. The
+/// field's internal storage name never changes.
+fn setter_ident(
+    field_name: &Ident,
+    setter_prefix: Option<&LitStr>,
+    rename: Option<&LitStr>,
+    case: Option<&SetterCase>,
+) -> Ident {
+    let prefixed = match setter_prefix {
+        Some(prefix) => format!("{}{}", prefix.value(), field_name),
+        None => field_name.to_string(),
+    };
+
+    if let Some(rename) = rename {
+        Ident::new(&rename.value(), rename.span())
+    } else if let Some(case) = case {
+        Ident::new(&case.convert(&prefixed), field_name.span())
+    } else {
+        Ident::new(&prefixed, field_name.span())
     }
-    None
 }
 
-#[derive(Debug)]
-struct BuilderAttr {
-    key: syn::Ident,
-    _eq_token: Token![=],
-    value: LitStr,
+/// `#[allow(non_snake_case)]` on a generated setter when a non-snake case
+/// policy is in effect, so a documented, opt-in naming convention doesn't
+/// produce unsolicited lint warnings for consumers.
+fn non_snake_allow(case: Option<&SetterCase>) -> Option<proc_macro2::TokenStream> {
+    case.filter(|case| case.is_non_snake())
+        .map(|_| quote! { #[allow(non_snake_case)] })
 }
 
-impl Parse for BuilderAttr {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        Ok(BuilderAttr {
-            key: input.parse()?,
-            _eq_token: input.parse()?,
-            value: input.parse()?,
+/// Folds a list of errors into a single `syn::Error` via `syn::Error::combine`,
+/// so every diagnostic is emitted in one `compile_error!` pass.
+fn combine_errors(errors: Vec<syn::Error>) -> Option<syn::Error> {
+    let mut errors = errors.into_iter();
+    let mut combined = errors.next()?;
+    for error in errors {
+        combined.combine(error);
+    }
+    Some(combined)
+}
+
+/// The tokens used to *reference* a declared generic parameter list, e.g.
+/// `<'a, T>` for a declaration of `<'a, T: Clone>`.
+fn generic_usage_args(generics: &syn::Generics) -> Vec<proc_macro2::TokenStream> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            syn::GenericParam::Lifetime(lifetime_param) => {
+                let lifetime = &lifetime_param.lifetime;
+                quote! { #lifetime }
+            }
+            syn::GenericParam::Type(type_param) => {
+                let ident = &type_param.ident;
+                quote! { #ident }
+            }
+            syn::GenericParam::Const(const_param) => {
+                let ident = &const_param.ident;
+                quote! { #ident }
+            }
         })
+        .collect()
+}
+
+/// `Ident<arg, arg, ...>`, or just `Ident` when `args` is empty.
+fn generic_path(ident: &Ident, args: &[proc_macro2::TokenStream]) -> proc_macro2::TokenStream {
+    if args.is_empty() {
+        quote! { #ident }
+    } else {
+        quote! { #ident<#(#args),*> }
     }
 }
 
-/// I don't want to think about, so why not
-enum EachAttrResult {
-    Success(String),
-    None,
-    Error(TokenStream),
+/// An unconstrained, default-less type parameter named `ident`, suitable for
+/// pushing onto a `syn::Generics` used only in an `impl` header.
+fn free_type_param(ident: Ident) -> syn::GenericParam {
+    syn::GenericParam::Type(syn::TypeParam {
+        attrs: Vec::new(),
+        ident,
+        colon_token: None,
+        bounds: syn::punctuated::Punctuated::new(),
+        eq_token: None,
+        default: None,
+    })
 }
 
-fn find_each_attr(attribute: &[syn::Attribute]) -> EachAttrResult {
-    if let Some(attr) = attribute.first() {
-        if let syn::Meta::List(meta_list) = &attr.meta {
-            if meta_list.path.is_ident("builder") {
-                // Attempt to parse the tokens
+/// Generates a `#[builder(typestate)]` builder: one generic type parameter
+/// per required field, defaulting to a zero-sized `Unset` marker, so that
+/// `build()` is only in scope once every required field has been set to
+/// `Set`. Optional, `Vec`, and `default`-backed fields are excluded from the
+/// type-state and keep a single, always-available setter.
+#[allow(clippy::too_many_arguments)]
+fn derive_typestate(
+    input: &DeriveInput,
+    struct_ident: &Ident,
+    builder_ident: &Ident,
+    fields: &syn::punctuated::Punctuated<syn::Field, Token![,]>,
+    field_opts: &[FieldOpts],
+    shapes: &[(Option<&'static str>, &syn::Type)],
+    default_exprs: &[Option<Expr>],
+    setters_case: Option<&SetterCase>,
+) -> proc_macro2::TokenStream {
+    let (orig_impl_generics, orig_ty_generics, orig_where_clause) = input.generics.split_for_impl();
+    let orig_args = generic_usage_args(&input.generics);
+
+    let unset_ident = format_ident!("{}Unset", struct_ident);
+    let set_ident = format_ident!("{}Set", struct_ident);
+
+    let required_idx: Vec<usize> = fields
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| {
+            let (_, shape_ty) = &shapes[*idx];
+            !is_a("Option", shape_ty) && !is_a("Vec", shape_ty) && default_exprs[*idx].is_none()
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let state_params: Vec<Ident> = (0..required_idx.len())
+        .map(|i| format_ident!("__S{}", i))
+        .collect();
+
+    // The builder's own declaration needs the extra state params to default
+    // to `Unset`; `Generics`'s plain `ToTokens` (unlike `split_for_impl`)
+    // keeps those defaults.
+    let mut struct_generics = input.generics.clone();
+    for state in &state_params {
+        struct_generics.params.push(syn::GenericParam::Type(syn::TypeParam {
+            attrs: Vec::new(),
+            ident: state.clone(),
+            colon_token: None,
+            bounds: syn::punctuated::Punctuated::new(),
+            eq_token: Some(Default::default()),
+            default: Some(syn::parse_quote!(#unset_ident)),
+        }));
+    }
 
-                match syn::parse::<BuilderAttr>(meta_list.tokens.clone().into()) {
-                    Ok(parsed) => {
-                        let key = parsed.key.to_string();
+    let all_unset_args: Vec<_> = orig_args
+        .iter()
+        .cloned()
+        .chain(state_params.iter().map(|_| quote! { #unset_ident }))
+        .collect();
+    let builder_all_unset = generic_path(builder_ident, &all_unset_args);
+
+    let all_set_args: Vec<_> = orig_args
+        .iter()
+        .cloned()
+        .chain(state_params.iter().map(|_| quote! { #set_ident }))
+        .collect();
+    let builder_all_set = generic_path(builder_ident, &all_set_args);
+
+    let has_marker = !state_params.is_empty();
+    let marker_field = has_marker.then(|| quote! { _marker: ::std::marker::PhantomData<(#(#state_params,)*)>, });
+    let marker_init = has_marker.then(|| quote! { _marker: ::std::marker::PhantomData, });
+
+    let builder_struct_fields = fields.iter().zip(shapes.iter()).map(|(field, (_, shape_ty))| {
+        let name = field.ident.as_ref().expect("Couldn't get the field");
 
-                        if key != "each" {
-                            let error = syn::Error::new_spanned(
-                                meta_list,
-                                "expected `builder(each = \"...\")`",
-                            );
+        if is_a("Option", shape_ty) {
+            quote! { #name: #shape_ty }
+        } else {
+            quote! { #name: ::std::option::Option<#shape_ty> }
+        }
+    });
 
-                            return EachAttrResult::Error(error.into_compile_error().into());
-                        }
+    let builder_init_fields = fields.iter().map(|field| {
+        let name = field.ident.as_ref().expect("Couldn't get the field");
+        quote! { #name: ::std::option::Option::None }
+    });
 
-                        return EachAttrResult::Success(parsed.value.value());
+    // One impl block per required field: only available while that field's
+    // state parameter is `Unset`, and only that parameter flips to `Set`.
+    let required_setters = required_idx.iter().enumerate().map(|(pos, &field_idx)| {
+        let field = &fields[field_idx];
+        let name = field.ident.as_ref().expect("Couldn't get the field");
+        let (_, shape_ty) = &shapes[field_idx];
+        let opts = &field_opts[field_idx];
+        let setter_name = setter_ident(name, opts.setter_prefix.as_ref(), opts.rename.as_ref(), setters_case);
+        let non_snake_allow = non_snake_allow(setters_case);
+
+        let mut impl_generics = input.generics.clone();
+        for (i, state) in state_params.iter().enumerate() {
+            if i != pos {
+                impl_generics.params.push(free_type_param(state.clone()));
+            }
+        }
+        let (impl_generics, _, impl_where_clause) = impl_generics.split_for_impl();
+
+        let self_args: Vec<_> = orig_args
+            .iter()
+            .cloned()
+            .chain(state_params.iter().enumerate().map(|(i, state)| {
+                if i == pos {
+                    quote! { #unset_ident }
+                } else {
+                    quote! { #state }
+                }
+            }))
+            .collect();
+        let self_ty = generic_path(builder_ident, &self_args);
+
+        let ret_args: Vec<_> = orig_args
+            .iter()
+            .cloned()
+            .chain(state_params.iter().enumerate().map(|(i, state)| {
+                if i == pos {
+                    quote! { #set_ident }
+                } else {
+                    quote! { #state }
+                }
+            }))
+            .collect();
+        let ret_ty = generic_path(builder_ident, &ret_args);
+
+        let other_names = fields
+            .iter()
+            .filter_map(|f| f.ident.as_ref())
+            .filter(|ident| *ident != name);
+
+        quote! {
+            impl #impl_generics #self_ty #impl_where_clause {
+                #non_snake_allow
+                pub fn #setter_name(self, #name: #shape_ty) -> #ret_ty {
+                    #builder_ident {
+                        #name: ::std::option::Option::Some(#name),
+                        #(#other_names: self.#other_names,)*
+                        #marker_init
                     }
+                }
+            }
+        }
+    });
+
+    // Optional, `Vec`, and `default`-backed fields get one setter, generic
+    // over every state parameter so it's available no matter what else has
+    // been set yet.
+    let optional_setters = fields
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !required_idx.contains(idx))
+        .map(|(idx, field)| {
+            let name = field.ident.as_ref().expect("Couldn't get the field");
+            let (_, shape_ty) = &shapes[idx];
+            let opts = &field_opts[idx];
+            let setter_name = setter_ident(name, opts.setter_prefix.as_ref(), opts.rename.as_ref(), setters_case);
+            let non_snake_allow = non_snake_allow(setters_case);
+
+            let mut impl_generics = input.generics.clone();
+            for state in &state_params {
+                impl_generics.params.push(free_type_param(state.clone()));
+            }
+            let (impl_generics, _, impl_where_clause) = impl_generics.split_for_impl();
 
-                    Err(_) => {
-                        let error = syn::Error::new_spanned(
-                            meta_list,
-                            "expected `builder(each = \"...\")`",
-                        );
+            let self_args: Vec<_> = orig_args
+                .iter()
+                .cloned()
+                .chain(state_params.iter().map(|state| quote! { #state }))
+                .collect();
+            let self_ty = generic_path(builder_ident, &self_args);
 
-                        return EachAttrResult::Error(error.into_compile_error().into());
+            if is_a("Option", shape_ty) {
+                let inner_ty = get_in_angle_bracket(shape_ty).unwrap();
+                quote! {
+                    impl #impl_generics #self_ty #impl_where_clause {
+                        #non_snake_allow
+                        pub fn #setter_name(mut self, #name: #inner_ty) -> Self {
+                            self.#name = ::std::option::Option::Some(#name);
+                            self
+                        }
+                    }
+                }
+            } else if is_a("Vec", shape_ty) && opts.each.is_some() {
+                let each = opts.each.as_ref().unwrap();
+                let each_ident = syn::Ident::new(&each.value(), each.span());
+                let inner_ty = get_in_angle_bracket(shape_ty).unwrap();
+
+                quote! {
+                    impl #impl_generics #self_ty #impl_where_clause {
+                        pub fn #each_ident(mut self, #each_ident: #inner_ty) -> Self {
+                            self.#name.get_or_insert_with(Vec::new).push(#each_ident);
+                            self
+                        }
+                    }
+                }
+            } else {
+                quote! {
+                    impl #impl_generics #self_ty #impl_where_clause {
+                        #non_snake_allow
+                        pub fn #setter_name(mut self, #name: #shape_ty) -> Self {
+                            self.#name = ::std::option::Option::Some(#name);
+                            self
+                        }
                     }
                 }
             }
+        });
+
+    let build_fields = fields.iter().enumerate().map(|(idx, field)| {
+        let name = field.ident.as_ref().expect("Couldn't get the field");
+        let (wrapper, shape_ty) = &shapes[idx];
+
+        let value = if required_idx.contains(&idx) {
+            quote! { self.#name.expect("required field guaranteed set by the builder's type state") }
+        } else if is_a("Option", shape_ty) {
+            quote! { self.#name }
+        } else if is_a("Vec", shape_ty) {
+            quote! { self.#name.unwrap_or_default() }
+        } else {
+            let default_expr = default_exprs[idx]
+                .as_ref()
+                .expect("non-required, non-Option, non-Vec fields always have a default");
+            quote! { self.#name.unwrap_or_else(|| #default_expr) }
+        };
+
+        let value = match wrapper {
+            Some(wrapper) => rewrap(wrapper, value),
+            None => value,
+        };
+
+        quote! { #name: #value }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        pub struct #unset_ident;
+        #[doc(hidden)]
+        pub struct #set_ident;
+
+        pub struct #builder_ident #struct_generics #orig_where_clause {
+            #(#builder_struct_fields,)*
+            #marker_field
         }
-    }
 
-    EachAttrResult::None
+        impl #orig_impl_generics #struct_ident #orig_ty_generics #orig_where_clause {
+            pub fn builder() -> #builder_all_unset {
+                #builder_ident {
+                    #(#builder_init_fields,)*
+                    #marker_init
+                }
+            }
+        }
+
+        #(#required_setters)*
+
+        #(#optional_setters)*
+
+        impl #orig_impl_generics #builder_all_set #orig_where_clause {
+            pub fn build(self) -> #struct_ident #orig_ty_generics {
+                #struct_ident {
+                    #(#build_fields,)*
+                }
+            }
+        }
+    }
 }
 
 #[proc_macro_derive(Builder, attributes(builder))]
 pub fn derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
-    println!("{:#?}", input);
+    let mut errors = Vec::new();
+
+    let struct_opts = match StructOpts::from_attrs(&input.attrs) {
+        Ok(opts) => opts,
+        Err(err) => {
+            errors.push(err);
+            StructOpts::default()
+        }
+    };
 
     let struct_ident = &input.ident;
 
     let builder_ident = Ident::new(&format!("{}Builder", struct_ident), struct_ident.span());
 
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
     let fields = match &input.data {
         syn::Data::Struct(data_struct) => match &data_struct.fields {
-            syn::Fields::Named(fields) => &fields.named,
-            _ => panic!("Only structs with named fields are supported"),
+            syn::Fields::Named(fields) => Some(&fields.named),
+            other => {
+                errors.push(syn::Error::new_spanned(
+                    other,
+                    "Builder only supports structs with named fields",
+                ));
+                None
+            }
         },
-        _ => panic!("Only structs are supported"),
+        syn::Data::Enum(data_enum) => {
+            errors.push(syn::Error::new_spanned(
+                data_enum.enum_token,
+                "Builder only supports structs with named fields",
+            ));
+            None
+        }
+        syn::Data::Union(data_union) => {
+            errors.push(syn::Error::new_spanned(
+                data_union.union_token,
+                "Builder only supports structs with named fields",
+            ));
+            None
+        }
     };
-    // First, check for any errors in attributes
 
-    let mut error_tokens = TokenStream::new();
+    // If the input isn't even the right shape, there's nothing left to check.
+    let Some(fields) = fields else {
+        return combine_errors(errors)
+            .expect("shape mismatch always pushes an error")
+            .to_compile_error()
+            .into();
+    };
+
+    // Parse every field's `#[builder(...)]` attributes, and check that we can
+    // introspect the types we need to introspect, collecting every mistake
+    // instead of bailing on the first.
+
+    let mut field_opts = Vec::with_capacity(fields.len());
 
     for field in fields {
-        if is_a("Vec".into(), &field.ty) {
-            if let EachAttrResult::Error(error) = find_each_attr(&field.attrs) {
-                error_tokens.extend(error);
+        match FieldOpts::from_attrs(&field.attrs) {
+            Ok(opts) => field_opts.push(opts),
+            Err(err) => {
+                errors.push(err);
+                field_opts.push(FieldOpts::default());
             }
         }
     }
 
-    if !error_tokens.is_empty() {
-        return error_tokens;
-    }
+    // The "shape" of a field is the type used to decide whether it's an
+    // `Option`, a `Vec`, or plain required -- peeled of one layer of
+    // transparent smart pointer, so `Box<Option<String>>` is classified the
+    // same as `Option<String>`.
+    let shapes: Vec<(Option<&'static str>, &syn::Type)> = fields
+        .iter()
+        .map(|field| match unwrap_transparent(&field.ty) {
+            Some((wrapper, inner)) => (Some(wrapper), inner),
+            None => (None, &field.ty),
+        })
+        .collect();
 
-    let builder_fields = fields.iter().map(|field| {
-        let name = field.ident.as_ref().expect("Couldn't get the field");
-        let ty = &field.ty;
+    for ((_, shape_ty), opts) in shapes.iter().zip(field_opts.iter()) {
+        let is_option = is_a("Option", shape_ty);
+        let is_vec = is_a("Vec", shape_ty);
 
-        let is_option = is_a("Option".into(), ty);
+        if (is_option || (is_vec && opts.each.is_some())) && get_in_angle_bracket(shape_ty).is_none()
+        {
+            errors.push(syn::Error::new_spanned(
+                shape_ty,
+                "could not determine the inner type of this generic wrapper",
+            ));
+        }
 
-        if is_option {
-            quote! {
-                #name: #ty
+        // `Option`/`Vec` fields already fall back to `None`/`vec![]` without a
+        // setter, so a `default` on top of one would either be silently
+        // discarded or ambiguous about which fallback should win -- reject it
+        // instead of picking one silently.
+        if opts.default.is_some() && (is_option || is_vec) {
+            errors.push(syn::Error::new_spanned(
+                shape_ty,
+                "#[builder(default = ...)] is redundant on Option/Vec fields, which already default to None/vec![] without a setter",
+            ));
+        }
+    }
+
+    // Parse `default = "expr"` literals into expressions up front, so a bad
+    // expression is reported alongside every other mistake in this pass.
+    let default_exprs: Vec<Option<Expr>> = field_opts
+        .iter()
+        .map(|opts| match &opts.default {
+            Some(DefaultValue::Expr(lit)) => match lit.parse::<Expr>() {
+                Ok(expr) => Some(expr),
+                Err(err) => {
+                    errors.push(err);
+                    None
+                }
+            },
+            Some(DefaultValue::Bare) => {
+                Some(syn::parse_quote!(::core::default::Default::default()))
             }
-        } else {
-            quote! {
-                #name: ::std::option::Option<#ty>
+            None => None,
+        })
+        .collect();
+
+    // Validate the struct-level setter case policy, if any, alongside every
+    // other mistake in this pass.
+    let setters_case: Option<SetterCase> = match &struct_opts.setters {
+        Some(lit) => match SetterCase::parse(&lit.value()) {
+            Some(case) => Some(case),
+            None => {
+                errors.push(syn::Error::new_spanned(lit, "unknown builder setters policy"));
+                None
             }
-        }
-    });
+        },
+        None => None,
+    };
 
-    let builder_setters = fields.iter().map(|field| {
+    if let Some(error) = combine_errors(errors) {
+        return error.to_compile_error().into();
+    }
+
+    if struct_opts.typestate {
+        return derive_typestate(
+            &input,
+            struct_ident,
+            &builder_ident,
+            fields,
+            &field_opts,
+            &shapes,
+            &default_exprs,
+            setters_case.as_ref(),
+        )
+        .into();
+    }
+
+    let builder_fields = fields.iter().zip(shapes.iter()).map(|(field, (_, shape_ty))| {
         let name = field.ident.as_ref().expect("Couldn't get the field");
-        let ty = &field.ty;
 
-        let is_option = is_a("Option".into(), ty);
+        let is_option = is_a("Option", shape_ty);
 
         if is_option {
-            let inner_ty = get_in_angle_bracket(ty).unwrap();
             quote! {
-                pub fn #name(&mut self, #name: #inner_ty) -> &mut Self {
-                    self.#name = ::std::option::Option::Some(#name);
-                    self
-                    }
+                #name: #shape_ty
             }
         } else {
-            let each_attr = find_each_attr(&field.attrs);
-
-            let each_attr = match each_attr {
-                EachAttrResult::Success(s) => Some(s),
-                EachAttrResult::None => None,
-                _ => None,
-            };
-
-            let is_vec = is_a("Vec".into(), ty);
+            quote! {
+                #name: ::std::option::Option<#shape_ty>
+            }
+        }
+    });
 
-            if each_attr.is_some() && is_vec {
-                let each_value = each_attr.unwrap();
-                let each_ident = syn::Ident::new(&each_value, name.span());
+    let builder_setters = fields
+        .iter()
+        .zip(shapes.iter())
+        .zip(field_opts.iter())
+        .map(|((field, (_, shape_ty)), opts)| {
+            let name = field.ident.as_ref().expect("Couldn't get the field");
+            let setter_name = setter_ident(name, opts.setter_prefix.as_ref(), opts.rename.as_ref(), setters_case.as_ref());
+            let non_snake_allow = non_snake_allow(setters_case.as_ref());
 
-                let inside_vec_value = get_in_angle_bracket(ty).unwrap();
+            let is_option = is_a("Option", shape_ty);
 
+            if is_option {
+                let inner_ty = get_in_angle_bracket(shape_ty).unwrap();
                 quote! {
-                    pub fn #each_ident(&mut self, #each_ident: #inside_vec_value) -> &mut Self {
-                        self.#name.get_or_insert_with(Vec::new).push(#each_ident);
+                    #non_snake_allow
+                    pub fn #setter_name(&mut self, #name: #inner_ty) -> &mut Self {
+                        self.#name = ::std::option::Option::Some(#name);
                         self
-                    }
+                        }
                 }
             } else {
-                quote! {
-                    pub fn #name(&mut self, #name: #ty) -> &mut Self {
-                        self.#name = ::std::option::Option::Some(#name);
-                        self
+                let is_vec = is_a("Vec", shape_ty);
+
+                if let (true, Some(each)) = (is_vec, opts.each.as_ref()) {
+                    let each_ident = syn::Ident::new(&each.value(), each.span());
+
+                    let inside_vec_value = get_in_angle_bracket(shape_ty).unwrap();
+
+                    quote! {
+                        pub fn #each_ident(&mut self, #each_ident: #inside_vec_value) -> &mut Self {
+                            self.#name.get_or_insert_with(Vec::new).push(#each_ident);
+                            self
+                        }
+                    }
+                } else {
+                    quote! {
+                        #non_snake_allow
+                        pub fn #setter_name(&mut self, #name: #shape_ty) -> &mut Self {
+                            self.#name = ::std::option::Option::Some(#name);
+                            self
+                        }
                     }
                 }
             }
-        }
-    });
+        });
 
     let builder_body = fields.iter().map(|field| {
         let name = field.ident.as_ref().expect("Couldn't get the field");
@@ -211,47 +776,54 @@ pub fn derive(input: TokenStream) -> TokenStream {
         }
     });
 
-    let build_body = fields.iter().map(|field| {
-        let name = field.ident.as_ref().expect("Couldn't get the field");
-        let name_str = name.to_string();
-
-        let ty = &field.ty;
+    let build_body = fields
+        .iter()
+        .zip(shapes.iter())
+        .zip(default_exprs.iter())
+        .map(|((field, (wrapper, shape_ty)), default_expr)| {
+            let name = field.ident.as_ref().expect("Couldn't get the field");
+            let name_str = name.to_string();
+
+            let is_option = is_a("Option", shape_ty);
+            let is_vec = is_a("Vec", shape_ty);
+
+            let value = if is_option {
+                quote! { self.#name.clone() }
+            } else if is_vec {
+                quote! { self.#name.take().unwrap_or_default() }
+            } else if let Some(default_expr) = default_expr {
+                quote! { self.#name.take().unwrap_or_else(|| #default_expr) }
+            } else {
+                quote! { self.#name.take().ok_or_else(|| format!("Field '{}' is not set", #name_str))? }
+            };
 
-        let is_option = is_a("Option".into(), ty);
-        let is_vec = is_a("Vec".into(), ty);
+            let value = match wrapper {
+                Some(wrapper) => rewrap(wrapper, value),
+                None => value,
+            };
 
-        if is_option {
-            quote! {
-                #name: self.#name.clone()
-            }
-        } else if is_vec {
             quote! {
-                #name: self.#name.take().unwrap_or_default()
+                #name: #value
             }
-        } else {
-            quote! {
-                #name: self.#name.take().ok_or_else(|| format!("Field '{}' is not set", #name_str))?
-            }
-        }
-    });
+        });
 
     quote! {
-        pub struct #builder_ident {
+        pub struct #builder_ident #impl_generics #where_clause {
             #(#builder_fields,)*
         }
 
-        impl #struct_ident {
-            pub fn builder() -> #builder_ident {
+        impl #impl_generics #struct_ident #ty_generics #where_clause {
+            pub fn builder() -> #builder_ident #ty_generics {
                 #builder_ident {
                     #(#builder_body,)*
                 }
             }
         }
 
-        impl #builder_ident {
+        impl #impl_generics #builder_ident #ty_generics #where_clause {
             #(#builder_setters)*
 
-            pub fn build(&mut self) -> ::std::result::Result<#struct_ident, ::std::boxed::Box<(dyn ::std::error::Error + 'static)>> {
+            pub fn build(&mut self) -> ::std::result::Result<#struct_ident #ty_generics, ::std::boxed::Box<(dyn ::std::error::Error + 'static)>> {
                 ::std::result::Result::Ok(#struct_ident {
                     #(#build_body,)*
                 })
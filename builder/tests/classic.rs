@@ -0,0 +1,159 @@
+//! Integration tests for the classic (non-`#[builder(typestate)]`) builder,
+//! the default code path every existing user hits. `tests/typestate.rs`
+//! covers the type-state opt-in; this file exercises the same surface area
+//! against `build(&mut self) -> Result<...>` instead.
+
+use builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+struct Command<'a, T>
+where
+    T: Clone,
+{
+    program: &'a str,
+    payload: T,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+}
+
+#[test]
+fn generics_lifetimes_and_where_clause() {
+    let cmd = Command::builder()
+        .program("ls")
+        .payload(42)
+        .arg("-la".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        cmd,
+        Command {
+            program: "ls",
+            payload: 42,
+            args: vec!["-la".to_owned()],
+        }
+    );
+}
+
+#[derive(Builder, Debug, PartialEq)]
+struct Qualified {
+    name: std::option::Option<String>,
+    id: core::option::Option<u32>,
+}
+
+#[test]
+fn fully_qualified_option_paths_are_recognized() {
+    // If `is_a("Option", ..)` only matched a bare `Option<T>`, these setters
+    // would take `Option<String>`/`Option<u32>` instead of the inner type
+    // and this wouldn't compile.
+    let q = Qualified::builder()
+        .name("crate".to_owned())
+        .id(1)
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        q,
+        Qualified {
+            name: Some("crate".to_owned()),
+            id: Some(1),
+        }
+    );
+}
+
+#[derive(Builder, Debug, PartialEq)]
+struct Wrapped {
+    id: Box<u32>,
+    tags: std::sync::Arc<Vec<String>>,
+    label: std::rc::Rc<String>,
+}
+
+#[test]
+fn transparent_wrappers_are_rewrapped_on_build() {
+    let wrapped = Wrapped::builder()
+        .id(1)
+        .tags(vec!["a".to_owned()])
+        .label("x".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(*wrapped.id, 1);
+    assert_eq!(*wrapped.tags, vec!["a".to_owned()]);
+    assert_eq!(*wrapped.label, "x");
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(setters = "camelCase")]
+struct Renamed {
+    #[builder(rename = "identifier")]
+    id: u32,
+    display_name: String,
+}
+
+#[test]
+fn rename_wins_over_case_policy() {
+    let renamed = Renamed::builder()
+        .identifier(1)
+        .displayName("x".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        renamed,
+        Renamed {
+            id: 1,
+            display_name: "x".to_owned(),
+        }
+    );
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(setters = "PascalCase")]
+struct Pascal {
+    request_url: String,
+}
+
+#[test]
+fn pascal_case_setters_compile_without_lint_warnings() {
+    // The derive emits `#[allow(non_snake_case)]` on this setter; if it
+    // didn't, `cargo build -D warnings` on a consumer crate would fail.
+    let p = Pascal::builder().RequestUrl("y".to_owned()).build().unwrap();
+    assert_eq!(p.request_url, "y");
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(setters = "camelCase")]
+struct MultiAttr {
+    // Two separate `#[builder(...)]` attributes on one field must both be
+    // walked by `FieldOpts::from_attrs`, not just the first: the prefix from
+    // one attribute and the default from the other both need to take
+    // effect, and the prefixed name still goes through the case policy.
+    #[builder(setter_prefix = "set_")]
+    #[builder(default = "7")]
+    count: u32,
+}
+
+#[test]
+fn multiple_builder_attributes_on_one_field_are_all_applied() {
+    let explicit = MultiAttr::builder().setCount(3).build().unwrap();
+    assert_eq!(explicit, MultiAttr { count: 3 });
+
+    let defaulted = MultiAttr::builder().build().unwrap();
+    assert_eq!(defaulted, MultiAttr { count: 7 });
+}
+
+#[derive(Builder, Debug, PartialEq)]
+struct NoAttrs {
+    name: String,
+}
+
+#[test]
+fn field_without_any_builder_attribute_uses_defaults() {
+    // No `#[builder(...)]` at all on `name` exercises the
+    // default-filled-`FieldOpts` path in `FieldOpts::from_attrs`.
+    let err = NoAttrs::builder().build().unwrap_err();
+    assert!(err.to_string().contains("name"));
+
+    let ok = NoAttrs::builder().name("x".to_owned()).build().unwrap();
+    assert_eq!(ok.name, "x");
+}
@@ -0,0 +1,128 @@
+//! Integration tests for `#[builder(typestate)]`. These exercise the
+//! generated type-state machinery end to end (as opposed to `src/lib.rs`'s
+//! unit-level helpers, which have no state of their own to test): required
+//! fields gating `build()`, optional/`Vec`/`default` fields staying always
+//! available, generic structs with bounds, transparent smart pointers, and
+//! empty structs.
+
+use builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    env: Option<String>,
+    #[builder(default = "2")]
+    retries: u8,
+}
+
+#[test]
+fn required_field_gates_build() {
+    // `build()` only appears once `executable` has been set, so this chain
+    // would fail to *compile* (not just assert wrong) if the state
+    // parameter threading in `derive_typestate` were broken.
+    let cmd = Command::builder()
+        .executable("ls".to_owned())
+        .arg("-la".to_owned())
+        .env("PATH=/bin".to_owned())
+        .build();
+
+    assert_eq!(
+        cmd,
+        Command {
+            executable: "ls".to_owned(),
+            args: vec!["-la".to_owned()],
+            env: Some("PATH=/bin".to_owned()),
+            retries: 2,
+        }
+    );
+}
+
+#[test]
+fn optional_vec_and_default_fields_need_no_setter() {
+    let cmd = Command::builder().executable("true".to_owned()).build();
+
+    assert_eq!(
+        cmd,
+        Command {
+            executable: "true".to_owned(),
+            args: Vec::new(),
+            env: None,
+            retries: 2,
+        }
+    );
+}
+
+#[test]
+fn default_can_still_be_overridden() {
+    let cmd = Command::builder()
+        .executable("false".to_owned())
+        .retries(5)
+        .build();
+
+    assert_eq!(cmd.retries, 5);
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+struct Pair<T: Clone> {
+    left: T,
+    right: T,
+}
+
+#[test]
+fn generic_struct_gates_both_required_fields() {
+    let pair = Pair::builder().left(1).right(2).build();
+    assert_eq!(pair, Pair { left: 1, right: 2 });
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+struct Wrapped {
+    id: Box<u32>,
+    tags: std::sync::Arc<Vec<String>>,
+}
+
+#[test]
+fn transparent_wrappers_are_rewrapped_on_build() {
+    let wrapped = Wrapped::builder().id(1).tags(vec!["a".to_owned()]).build();
+
+    assert_eq!(*wrapped.id, 1);
+    assert_eq!(*wrapped.tags, vec!["a".to_owned()]);
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+#[builder(setters = "camelCase")]
+struct Renamed {
+    #[builder(rename = "identifier")]
+    id: u32,
+    display_name: String,
+}
+
+#[test]
+fn rename_wins_over_case_policy() {
+    let renamed = Renamed::builder()
+        .identifier(1)
+        .displayName("x".to_owned())
+        .build();
+
+    assert_eq!(
+        renamed,
+        Renamed {
+            id: 1,
+            display_name: "x".to_owned(),
+        }
+    );
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(typestate)]
+struct Empty {}
+
+#[test]
+fn empty_struct_has_no_required_state() {
+    assert_eq!(Empty::builder().build(), Empty {});
+}
@@ -0,0 +1,9 @@
+use builder::Builder;
+
+#[derive(Builder)]
+struct Thing {
+    #[builder(default = "Some(3)")]
+    count: Option<i32>,
+}
+
+fn main() {}
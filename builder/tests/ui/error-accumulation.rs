@@ -0,0 +1,11 @@
+use builder::Builder;
+
+#[derive(Builder)]
+struct Thing {
+    #[builder(not_a_real_key = "x")]
+    name: String,
+    #[builder(default = "not valid rust (")]
+    count: u32,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use builder::Builder;
+
+#[derive(Builder)]
+struct Thing {
+    #[builder(not_a_real_key = "x")]
+    name: String,
+}
+
+fn main() {}